@@ -3,7 +3,7 @@ use bindgen::callbacks::{
     EnumVariantCustomBehavior, EnumVariantValue, IntKind, MacroParsingBehavior, ParseCallbacks,
 };
 use regex::Regex;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 
 #[derive(Debug)]
@@ -100,26 +100,443 @@ fn search_include(include_paths: &[PathBuf], header: &str) -> String {
     format!("/usr/include/{}", header)
 }
 
+fn feature_enabled(name: &str) -> bool {
+    env::var(format!("CARGO_FEATURE_{}", name)).is_ok()
+}
 
-fn thread_main() {
-    let ffmpeg_dir_env = env::var("FFMPEG_DIR").unwrap();
-    let ffmpeg_dir = PathBuf::from(ffmpeg_dir_env);
-    let emsdk_path = PathBuf::from(env::var("EMSDK").unwrap());
-    let emsdk_sysroot = emsdk_path.join("upstream/emscripten/cache/sysroot");
-    let include_paths = vec![ffmpeg_dir.join("include"), emsdk_sysroot.join("include")];
-    let src_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
-    println!("cargo:rustc-link-lib=static=avcodec");
-    println!("cargo:rustc-link-lib=static=avfilter");
-    println!("cargo:rustc-link-lib=static=avformat");
-    println!("cargo:rustc-link-lib=static=avutil");
-    println!("cargo:rustc-link-lib=static=swresample");
-    // println!("cargo:rustc-link-lib=static=c-wasm");
-    // println!("cargo:rustc-link-lib=static=c-builtins");
-    // println!("cargo:rustc-link-lib=static=vpx");
-    // println!("cargo:rustc-link-lib=static=aom");
-    println!("cargo:rustc-link-search=native={}/lib", ffmpeg_dir.to_string_lossy());
+/// Whether the crate should pull in the Emscripten sysroot, the mandatory
+/// `emscripten.h` header, and `EMSDK`-relative include paths. False on every
+/// native target, so `FFMPEG_DIR`/`EMSDK` being set doesn't drag wasm-only
+/// setup into a desktop build.
+fn targeting_emscripten() -> bool {
+    env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("emscripten")
+}
+
+/// Whether we link FFmpeg's libraries statically or dynamically, selected
+/// via `FFMPEG_SYS_LINK=static|dylib` and defaulting to the crate's
+/// historical static-linking behavior.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LinkMode {
+    Static,
+    Dylib,
+}
+
+impl LinkMode {
+    fn from_env() -> Self {
+        match env::var("FFMPEG_SYS_LINK").as_deref() {
+            Ok("dylib") => LinkMode::Dylib,
+            _ => LinkMode::Static,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LinkMode::Static => "static",
+            LinkMode::Dylib => "dylib",
+        }
+    }
+}
+
+/// FFmpeg releases in ascending order, each paired with the `libavutil`
+/// version it first shipped in. Several releases can share an avutil major
+/// (e.g. 4.0..4.4 are all avutil 56), so the minor is part of the key too —
+/// otherwise detecting FFmpeg 4.0 would also light up the `ffmpeg_4_4` gate.
+const FFMPEG_RELEASES: &[(u32, u32, u32, u32)] = &[
+    // (ffmpeg_major, ffmpeg_minor, avutil_major, avutil_minor)
+    (4, 0, 56, 14),
+    (4, 1, 56, 22),
+    (4, 2, 56, 31),
+    (4, 3, 56, 51),
+    (4, 4, 56, 70),
+    (5, 0, 57, 17),
+    (5, 1, 57, 24),
+    (6, 0, 58, 2),
+    (6, 1, 58, 7),
+];
+
+/// Parses `#define LIBAVUTIL_VERSION_{MAJOR,MINOR,MICRO}` out of the
+/// contents of `libavutil/version.h`.
+fn parse_avutil_version(contents: &str) -> Option<(u32, u32, u32)> {
+    let re = Regex::new(r"#define\s+LIBAVUTIL_VERSION_(MAJOR|MINOR|MICRO)\s+(\d+)").unwrap();
+
+    let mut major = None;
+    let mut minor = None;
+    let mut micro = None;
+    for cap in re.captures_iter(contents) {
+        let value: u32 = cap[2].parse().ok()?;
+        match &cap[1] {
+            "MAJOR" => major = Some(value),
+            "MINOR" => minor = Some(value),
+            "MICRO" => micro = Some(value),
+            _ => unreachable!(),
+        }
+    }
+    Some((major?, minor?, micro?))
+}
+
+/// Locates and parses `libavutil/version.h`, returning `None` if it can't be
+/// found or parsed (in which case callers should fall back to unversioned
+/// behavior).
+fn detect_avutil_version(include_paths: &[PathBuf]) -> Option<(u32, u32, u32)> {
+    let path = search_include(include_paths, "libavutil/version.h");
+    let contents = fs::read_to_string(path).ok()?;
+    parse_avutil_version(&contents)
+}
+
+/// The `(ffmpeg_major, ffmpeg_minor)` gates that apply to a detected
+/// `(avutil_major, avutil_minor)` pair: every release at or below it.
+fn enabled_releases(avutil_major: u32, avutil_minor: u32) -> Vec<(u32, u32)> {
+    FFMPEG_RELEASES
+        .iter()
+        .filter(|&&(_, _, release_major, release_minor)| {
+            (avutil_major, avutil_minor) >= (release_major, release_minor)
+        })
+        .map(|&(major, minor, _, _)| (major, minor))
+        .collect()
+}
+
+/// Emits `cargo:rustc-cfg` lines for every FFmpeg release at or below the
+/// detected `libavutil` version, plus a fork marker: FFmpeg keeps the micro
+/// version at 100+, while the Libav fork stays below that.
+fn emit_version_cfg(avutil_major: u32, avutil_minor: u32, avutil_micro: u32) {
+    for (major, minor) in enabled_releases(avutil_major, avutil_minor) {
+        println!("cargo:rustc-cfg=feature=\"ffmpeg_{}_{}\"", major, minor);
+        println!("cargo:rustc-cfg=ffmpeg_{}_{}", major, minor);
+    }
+
+    if avutil_micro >= 100 {
+        println!("cargo:rustc-cfg=ffmpeg_fork=\"ffmpeg\"");
+    } else {
+        println!("cargo:rustc-cfg=ffmpeg_fork=\"libav\"");
+    }
+}
+
+/// A header that bindgen should parse, optionally restricted to the range of
+/// `libavutil` major versions it actually ships in. `None` means unbounded.
+struct Header {
+    path: &'static str,
+    min_version: Option<u32>,
+    max_version: Option<u32>,
+}
+
+impl Header {
+    const fn new(path: &'static str) -> Self {
+        Header {
+            path,
+            min_version: None,
+            max_version: None,
+        }
+    }
+
+    const fn ranged(path: &'static str, min_version: Option<u32>, max_version: Option<u32>) -> Self {
+        Header {
+            path,
+            min_version,
+            max_version,
+        }
+    }
+
+    fn enabled_for(&self, avutil_version: Option<u32>) -> bool {
+        match avutil_version {
+            None => true,
+            Some(version) => {
+                self.min_version.is_none_or(|min| version >= min)
+                    && self.max_version.is_none_or(|max| version <= max)
+            }
+        }
+    }
+}
+
+/// One FFmpeg library and the headers bindgen should pull in for it.
+/// `features` lists the `CARGO_FEATURE_*` suffixes that must all be enabled
+/// for `optional` libraries; non-optional libraries are always included.
+struct Library {
+    name: &'static str,
+    optional: bool,
+    features: &'static [&'static str],
+    headers: &'static [Header],
+}
+
+impl Library {
+    fn is_enabled(&self) -> bool {
+        !self.optional || self.features.iter().all(|feature| feature_enabled(feature))
+    }
+}
+
+static LIBRARIES: &[Library] = &[
+    Library {
+        name: "avutil",
+        optional: false,
+        features: &[],
+        headers: &[
+            Header::new("libavutil/adler32.h"),
+            Header::new("libavutil/aes.h"),
+            Header::new("libavutil/audio_fifo.h"),
+            Header::new("libavutil/base64.h"),
+            Header::new("libavutil/blowfish.h"),
+            Header::new("libavutil/bprint.h"),
+            Header::new("libavutil/buffer.h"),
+            Header::new("libavutil/camellia.h"),
+            Header::new("libavutil/cast5.h"),
+            Header::new("libavutil/channel_layout.h"),
+            Header::new("libavutil/cpu.h"),
+            Header::new("libavutil/crc.h"),
+            Header::new("libavutil/dict.h"),
+            Header::new("libavutil/display.h"),
+            Header::new("libavutil/downmix_info.h"),
+            Header::new("libavutil/error.h"),
+            Header::new("libavutil/eval.h"),
+            Header::new("libavutil/fifo.h"),
+            Header::new("libavutil/file.h"),
+            Header::new("libavutil/frame.h"),
+            Header::new("libavutil/hash.h"),
+            Header::new("libavutil/hmac.h"),
+            Header::new("libavutil/imgutils.h"),
+            Header::new("libavutil/lfg.h"),
+            Header::new("libavutil/log.h"),
+            Header::new("libavutil/lzo.h"),
+            Header::new("libavutil/macros.h"),
+            Header::new("libavutil/mathematics.h"),
+            Header::new("libavutil/md5.h"),
+            Header::new("libavutil/mem.h"),
+            Header::new("libavutil/motion_vector.h"),
+            Header::new("libavutil/murmur3.h"),
+            Header::new("libavutil/opt.h"),
+            Header::new("libavutil/parseutils.h"),
+            Header::new("libavutil/pixdesc.h"),
+            Header::new("libavutil/pixfmt.h"),
+            Header::new("libavutil/random_seed.h"),
+            Header::new("libavutil/rational.h"),
+            Header::new("libavutil/replaygain.h"),
+            Header::new("libavutil/ripemd.h"),
+            Header::new("libavutil/samplefmt.h"),
+            Header::new("libavutil/sha.h"),
+            Header::new("libavutil/sha512.h"),
+            Header::new("libavutil/stereo3d.h"),
+            Header::new("libavutil/avstring.h"),
+            Header::new("libavutil/threadmessage.h"),
+            Header::new("libavutil/time.h"),
+            Header::new("libavutil/timecode.h"),
+            Header::new("libavutil/twofish.h"),
+            Header::new("libavutil/avutil.h"),
+            Header::new("libavutil/xtea.h"),
+            Header::new("libavutil/hwcontext.h"),
+        ],
+    },
+    Library {
+        name: "avutil",
+        optional: true,
+        features: &["LIB_DRM"],
+        // hwcontext_drm.h showed up alongside the DRM hwcontext backend and
+        // isn't present on older trees.
+        headers: &[Header::ranged("libavutil/hwcontext_drm.h", Some(54), None)],
+    },
+    Library {
+        name: "avcodec",
+        optional: true,
+        features: &["AVCODEC"],
+        headers: &[
+            Header::new("libavcodec/avcodec.h"),
+            Header::new("libavcodec/dv_profile.h"),
+            Header::new("libavcodec/avfft.h"),
+            // vaapi.h was folded into the generic hwcontext API and dropped
+            // once libavutil reached 58 (FFmpeg 6).
+            Header::ranged("libavcodec/vaapi.h", None, Some(57)),
+            Header::new("libavcodec/vorbis_parser.h"),
+        ],
+    },
+    Library {
+        name: "avdevice",
+        optional: true,
+        features: &["AVDEVICE"],
+        headers: &[Header::new("libavdevice/avdevice.h")],
+    },
+    Library {
+        name: "avfilter",
+        optional: true,
+        features: &["AVFILTER"],
+        headers: &[
+            Header::new("libavfilter/buffersink.h"),
+            Header::new("libavfilter/buffersrc.h"),
+            Header::new("libavfilter/avfilter.h"),
+        ],
+    },
+    Library {
+        name: "avformat",
+        optional: true,
+        features: &["AVFORMAT"],
+        headers: &[
+            Header::new("libavformat/avformat.h"),
+            Header::new("libavformat/avio.h"),
+        ],
+    },
+    Library {
+        name: "avresample",
+        optional: true,
+        features: &["AVRESAMPLE"],
+        // avresample was removed from FFmpeg at 5.0 (libavutil 57), so it's
+        // only present through avutil 56.
+        headers: &[Header::ranged("libavresample/avresample.h", None, Some(56))],
+    },
+    Library {
+        name: "postproc",
+        optional: true,
+        features: &["POSTPROC"],
+        headers: &[Header::new("libpostproc/postprocess.h")],
+    },
+    Library {
+        name: "swresample",
+        optional: true,
+        features: &["SWRESAMPLE"],
+        headers: &[Header::new("libswresample/swresample.h")],
+    },
+    Library {
+        name: "swscale",
+        optional: true,
+        features: &["SWSCALE"],
+        headers: &[Header::new("libswscale/swscale.h")],
+    },
+];
+
+/// Emits one `cargo:rustc-link-lib` per enabled library in `LIBRARIES`, in
+/// the chosen `link_mode`. Two table entries can share a `name` (e.g. the
+/// DRM hwcontext headers ride on `avutil`), so each name is only linked once.
+fn emit_link_libs(link_mode: LinkMode) {
+    let mut linked = std::collections::HashSet::new();
+    for library in LIBRARIES {
+        if !library.is_enabled() || !linked.insert(library.name) {
+            continue;
+        }
+        println!("cargo:rustc-link-lib={}={}", link_mode.as_str(), library.name);
+    }
+}
+
+/// Translates the enabled `CARGO_FEATURE_*` set into FFmpeg `configure`
+/// `--enable-`/`--disable-` flags, one per distinct library in `LIBRARIES`
+/// (skipping `avutil`, which FFmpeg always builds).
+fn configure_flags() -> Vec<String> {
+    let mut flags = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for library in LIBRARIES {
+        if library.name == "avutil" || !seen.insert(library.name) {
+            continue;
+        }
+        if library.is_enabled() {
+            flags.push(format!("--enable-{}", library.name));
+        } else {
+            flags.push(format!("--disable-{}", library.name));
+        }
+    }
+    flags
+}
+
+/// Configures, builds, and installs FFmpeg from `FFMPEG_SYS_SOURCE_DIR` into
+/// `OUT_DIR`, returning the install prefix. Only runs when the `build`
+/// feature is enabled; otherwise callers are expected to point us at a
+/// prebuilt install via `FFMPEG_DIR` or pkg-config.
+fn build_ffmpeg_from_source(link_mode: LinkMode) -> PathBuf {
+    let source_dir = PathBuf::from(env::var("FFMPEG_SYS_SOURCE_DIR").expect(
+        "FFMPEG_SYS_SOURCE_DIR must point at an FFmpeg source tree when the `build` feature is enabled",
+    ));
+    let install_dir = output().join("ffmpeg-install");
+    let jobs = env::var("NUM_JOBS").unwrap_or_else(|_| "1".to_string());
+
+    let status = std::process::Command::new("./configure")
+        .current_dir(&source_dir)
+        .arg(format!("--prefix={}", install_dir.to_string_lossy()))
+        .arg(match link_mode {
+            LinkMode::Static => "--enable-static",
+            LinkMode::Dylib => "--enable-shared",
+        })
+        .args(configure_flags())
+        .status()
+        .expect("failed to run FFmpeg's configure script");
+    assert!(status.success(), "FFmpeg configure failed");
+
+    let status = std::process::Command::new("make")
+        .current_dir(&source_dir)
+        .arg(format!("-j{}", jobs))
+        .status()
+        .expect("failed to run make");
+    assert!(status.success(), "FFmpeg build failed");
+
+    let status = std::process::Command::new("make")
+        .current_dir(&source_dir)
+        .arg("install")
+        .status()
+        .expect("failed to run make install");
+    assert!(status.success(), "FFmpeg install failed");
+
+    install_dir
+}
+
+/// Where to find FFmpeg's headers (and, in the Emscripten case, its sysroot),
+/// resolved from a source build (the `build` feature), from the explicit env
+/// vars, or, failing those, from pkg-config.
+struct Discovery {
+    include_paths: Vec<PathBuf>,
+    emsdk_path: Option<PathBuf>,
+}
+
+/// Builds a `Discovery` around an FFmpeg install prefix, whether that
+/// prefix came from `FFMPEG_DIR` or from compiling a vendored source tree.
+fn discovery_from_prefix(prefix: &Path, link_mode: LinkMode) -> Discovery {
+    let emsdk_path = if targeting_emscripten() {
+        env::var("EMSDK").ok().map(PathBuf::from)
+    } else {
+        None
+    };
+
+    let mut include_paths = vec![prefix.join("include")];
+    if let Some(emsdk_path) = &emsdk_path {
+        include_paths.push(emsdk_path.join("upstream/emscripten/cache/sysroot/include"));
+    }
+
+    emit_link_libs(link_mode);
+    println!("cargo:rustc-link-search=native={}/lib", prefix.to_string_lossy());
+
+    Discovery {
+        include_paths,
+        emsdk_path,
+    }
+}
+
+fn discover() -> Discovery {
+    let link_mode = LinkMode::from_env();
 
+    if feature_enabled("BUILD") {
+        return discovery_from_prefix(&build_ffmpeg_from_source(link_mode), link_mode);
+    }
 
+    if let Ok(ffmpeg_dir_env) = env::var("FFMPEG_DIR") {
+        return discovery_from_prefix(&PathBuf::from(ffmpeg_dir_env), link_mode);
+    }
+
+    let mut include_paths = Vec::new();
+    let mut probed = std::collections::HashSet::new();
+    for library in LIBRARIES {
+        if !library.is_enabled() || !probed.insert(library.name) {
+            continue;
+        }
+        let package = format!("lib{}", library.name);
+        match pkg_config::Config::new()
+            .statik(link_mode == LinkMode::Static)
+            .probe(&package)
+        {
+            // `probe` already emits the cargo:rustc-link-lib/link-search directives.
+            Ok(found) => include_paths.extend(found.include_paths),
+            Err(err) => println!("cargo:warning=pkg-config probe for {} failed: {}", package, err),
+        }
+    }
+    Discovery {
+        include_paths,
+        emsdk_path: None,
+    }
+}
+
+fn thread_main() {
+    let discovery = discover();
+    let include_paths = discovery.include_paths.clone();
 
     let clang_includes = include_paths
         .iter()
@@ -132,8 +549,6 @@ fn thread_main() {
         .clang_args(clang_includes)
         // https://github.com/rust-lang/rust-bindgen/issues/1941
         .clang_arg("-fvisibility=default")
-        .clang_arg(format!("--sysroot={}", emsdk_sysroot.to_string_lossy()))
-        // .clang_arg(format!("-I{}", emsdk_sysroot.join("include").to_string_lossy()))
         .ctypes_prefix("libc")
         // https://github.com/rust-lang/rust-bindgen/issues/550
         .blocklist_type("max_align_t")
@@ -232,113 +647,38 @@ fn thread_main() {
         .parse_callbacks(Box::new(Callbacks));
 
 
-    builder = builder.header(search_include(&[emsdk_path.join("upstream/emscripten/system/include")], "emscripten.h"));
-
-    // The input headers we would like to generate
-    // bindings for.
-    if env::var("CARGO_FEATURE_AVCODEC").is_ok() {
+    if let Some(emsdk_path) = &discovery.emsdk_path {
         builder = builder
-            .header(search_include(&include_paths, "libavcodec/avcodec.h"))
-            .header(search_include(&include_paths, "libavcodec/dv_profile.h"))
-            .header(search_include(&include_paths, "libavcodec/avfft.h"))
-            .header(search_include(&include_paths, "libavcodec/vaapi.h"))
-            .header(search_include(&include_paths, "libavcodec/vorbis_parser.h"));
+            .clang_arg(format!(
+                "--sysroot={}",
+                emsdk_path.join("upstream/emscripten/cache/sysroot").to_string_lossy()
+            ))
+            .header(search_include(
+                &[emsdk_path.join("upstream/emscripten/system/include")],
+                "emscripten.h",
+            ));
     }
 
-    if env::var("CARGO_FEATURE_AVDEVICE").is_ok() {
-        builder = builder.header(search_include(&include_paths, "libavdevice/avdevice.h"));
+    let detected_version = detect_avutil_version(&include_paths);
+    if let Some((major, minor, micro)) = detected_version {
+        emit_version_cfg(major, minor, micro);
     }
+    let avutil_version = detected_version.map(|(major, _, _)| major);
 
-    if env::var("CARGO_FEATURE_AVFILTER").is_ok() {
-        println!("avfilter?");
-        builder = builder
-            .header(search_include(&include_paths, "libavfilter/buffersink.h"))
-            .header(search_include(&include_paths, "libavfilter/buffersrc.h"))
-            .header(search_include(&include_paths, "libavfilter/avfilter.h"));
-    }
-
-    if env::var("CARGO_FEATURE_AVFORMAT").is_ok() {
-        builder = builder
-            .header(search_include(&include_paths, "libavformat/avformat.h"))
-            .header(search_include(&include_paths, "libavformat/avio.h"));
-    }
-
-    if env::var("CARGO_FEATURE_AVRESAMPLE").is_ok() {
-        builder = builder.header(search_include(&include_paths, "libavresample/avresample.h"));
-    }
-
-
-    builder = builder
-        .header(search_include(&include_paths, "libavutil/adler32.h"))
-        .header(search_include(&include_paths, "libavutil/aes.h"))
-        .header(search_include(&include_paths, "libavutil/audio_fifo.h"))
-        .header(search_include(&include_paths, "libavutil/base64.h"))
-        .header(search_include(&include_paths, "libavutil/blowfish.h"))
-        .header(search_include(&include_paths, "libavutil/bprint.h"))
-        .header(search_include(&include_paths, "libavutil/buffer.h"))
-        .header(search_include(&include_paths, "libavutil/camellia.h"))
-        .header(search_include(&include_paths, "libavutil/cast5.h"))
-        .header(search_include(&include_paths, "libavutil/channel_layout.h"))
-        .header(search_include(&include_paths, "libavutil/cpu.h"))
-        .header(search_include(&include_paths, "libavutil/crc.h"))
-        .header(search_include(&include_paths, "libavutil/dict.h"))
-        .header(search_include(&include_paths, "libavutil/display.h"))
-        .header(search_include(&include_paths, "libavutil/downmix_info.h"))
-        .header(search_include(&include_paths, "libavutil/error.h"))
-        .header(search_include(&include_paths, "libavutil/eval.h"))
-        .header(search_include(&include_paths, "libavutil/fifo.h"))
-        .header(search_include(&include_paths, "libavutil/file.h"))
-        .header(search_include(&include_paths, "libavutil/frame.h"))
-        .header(search_include(&include_paths, "libavutil/hash.h"))
-        .header(search_include(&include_paths, "libavutil/hmac.h"))
-        .header(search_include(&include_paths, "libavutil/imgutils.h"))
-        .header(search_include(&include_paths, "libavutil/lfg.h"))
-        .header(search_include(&include_paths, "libavutil/log.h"))
-        .header(search_include(&include_paths, "libavutil/lzo.h"))
-        .header(search_include(&include_paths, "libavutil/macros.h"))
-        .header(search_include(&include_paths, "libavutil/mathematics.h"))
-        .header(search_include(&include_paths, "libavutil/md5.h"))
-        .header(search_include(&include_paths, "libavutil/mem.h"))
-        .header(search_include(&include_paths, "libavutil/motion_vector.h"))
-        .header(search_include(&include_paths, "libavutil/murmur3.h"))
-        .header(search_include(&include_paths, "libavutil/opt.h"))
-        .header(search_include(&include_paths, "libavutil/parseutils.h"))
-        .header(search_include(&include_paths, "libavutil/pixdesc.h"))
-        .header(search_include(&include_paths, "libavutil/pixfmt.h"))
-        .header(search_include(&include_paths, "libavutil/random_seed.h"))
-        .header(search_include(&include_paths, "libavutil/rational.h"))
-        .header(search_include(&include_paths, "libavutil/replaygain.h"))
-        .header(search_include(&include_paths, "libavutil/ripemd.h"))
-        .header(search_include(&include_paths, "libavutil/samplefmt.h"))
-        .header(search_include(&include_paths, "libavutil/sha.h"))
-        .header(search_include(&include_paths, "libavutil/sha512.h"))
-        .header(search_include(&include_paths, "libavutil/stereo3d.h"))
-        .header(search_include(&include_paths, "libavutil/avstring.h"))
-        .header(search_include(&include_paths, "libavutil/threadmessage.h"))
-        .header(search_include(&include_paths, "libavutil/time.h"))
-        .header(search_include(&include_paths, "libavutil/timecode.h"))
-        .header(search_include(&include_paths, "libavutil/twofish.h"))
-        .header(search_include(&include_paths, "libavutil/avutil.h"))
-        .header(search_include(&include_paths, "libavutil/xtea.h"))
-        .header(search_include(&include_paths, "libavutil/hwcontext.h"));
-
-    if env::var("CARGO_FEATURE_POSTPROC").is_ok() {
-        builder = builder.header(search_include(&include_paths, "libpostproc/postprocess.h"));
-    }
-
-    if env::var("CARGO_FEATURE_SWRESAMPLE").is_ok() {
-        builder = builder.header(search_include(&include_paths, "libswresample/swresample.h"));
-    }
-
-    if env::var("CARGO_FEATURE_SWSCALE").is_ok() {
-        builder = builder.header(search_include(&include_paths, "libswscale/swscale.h"));
-    }
-
-    if env::var("CARGO_FEATURE_LIB_DRM").is_ok() {
-        builder = builder.header(search_include(&include_paths, "libavutil/hwcontext_drm.h"))
+    // The input headers we would like to generate bindings for, driven by
+    // the declarative library table above instead of one-off feature checks.
+    for library in LIBRARIES {
+        if !library.is_enabled() {
+            continue;
+        }
+        for header in library.headers {
+            if !header.enabled_for(avutil_version) {
+                continue;
+            }
+            builder = builder.header(search_include(&include_paths, header.path));
+        }
     }
 
-
     // Finish the builder and generate the bindings.
     let mut bindings = builder
         .generate()
@@ -365,4 +705,95 @@ fn thread_main() {
     // Write the bindings to the $OUT_DIR/bindings.rs file.
     fs::write(output().join("bindings.rs"), &bindings)
         .expect("Couldn't write bindings!");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_enabled_for_respects_min_and_max() {
+        let header = Header::ranged("libavcodec/vaapi.h", Some(57), Some(58));
+        assert!(!header.enabled_for(Some(56)));
+        assert!(header.enabled_for(Some(57)));
+        assert!(header.enabled_for(Some(58)));
+        assert!(!header.enabled_for(Some(59)));
+    }
+
+    #[test]
+    fn header_enabled_for_is_unbounded_when_version_unknown() {
+        let header = Header::ranged("libavcodec/vaapi.h", Some(57), Some(58));
+        assert!(header.enabled_for(None));
+    }
+
+    #[test]
+    fn header_new_has_no_version_bounds() {
+        let header = Header::new("libavutil/mem.h");
+        assert!(header.enabled_for(Some(0)));
+        assert!(header.enabled_for(Some(1000)));
+    }
+
+    #[test]
+    fn library_is_enabled_when_not_optional() {
+        let library = Library {
+            name: "avutil",
+            optional: false,
+            features: &[],
+            headers: &[],
+        };
+        assert!(library.is_enabled());
+    }
+
+    #[test]
+    fn library_is_enabled_requires_every_listed_feature() {
+        const FEATURE: &str = "LIBRARY_IS_ENABLED_TEST_FEATURE";
+        let var = format!("CARGO_FEATURE_{}", FEATURE);
+        env::remove_var(&var);
+
+        let library = Library {
+            name: "swscale",
+            optional: true,
+            features: &[FEATURE],
+            headers: &[],
+        };
+        assert!(!library.is_enabled());
+
+        env::set_var(&var, "1");
+        assert!(library.is_enabled());
+        env::remove_var(&var);
+    }
+
+    #[test]
+    fn parse_avutil_version_reads_major_minor_micro() {
+        let header = "\
+            #define LIBAVUTIL_VERSION_MAJOR  57\n\
+            #define LIBAVUTIL_VERSION_MINOR  24\n\
+            #define LIBAVUTIL_VERSION_MICRO 100\n";
+        assert_eq!(parse_avutil_version(header), Some((57, 24, 100)));
+    }
+
+    #[test]
+    fn parse_avutil_version_is_none_when_incomplete() {
+        let header = "#define LIBAVUTIL_VERSION_MAJOR 57\n";
+        assert_eq!(parse_avutil_version(header), None);
+    }
+
+    #[test]
+    fn enabled_releases_does_not_collide_across_same_major_point_releases() {
+        // FFmpeg 4.0 ships avutil 56.14; later 4.x point releases bump the
+        // avutil minor further within the same avutil major.
+        let gates = enabled_releases(56, 14);
+        assert!(gates.contains(&(4, 0)));
+        assert!(!gates.contains(&(4, 1)));
+        assert!(!gates.contains(&(4, 4)));
+    }
+
+    #[test]
+    fn enabled_releases_includes_every_release_at_or_below() {
+        let gates = enabled_releases(57, 24);
+        assert!(gates.contains(&(4, 4)));
+        assert!(gates.contains(&(5, 0)));
+        assert!(gates.contains(&(5, 1)));
+        assert!(!gates.contains(&(6, 0)));
+    }
 }
\ No newline at end of file